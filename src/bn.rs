@@ -0,0 +1,165 @@
+//! A minimal 256-bit unsigned integer used to carry curve math through
+//! intermediate products that would otherwise overflow `u128`
+//!
+//! Only the operations the curve math needs are implemented: widening from
+//! `u128`, checked addition/subtraction, widening multiplication, division by
+//! a `u128` divisor, a checked narrowing back down to `u128`, and an integer
+//! square root.
+
+use crate::error::SwapError;
+
+/// A 256-bit unsigned integer, stored as high and low 128-bit halves
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, PartialOrd, Ord)]
+pub struct U256 {
+    hi: u128,
+    lo: u128,
+}
+
+impl U256 {
+    /// The zero value
+    pub const ZERO: U256 = U256 { hi: 0, lo: 0 };
+
+    /// Widen a `u128` into a `U256`
+    pub fn from_u128(val: u128) -> Self {
+        Self { hi: 0, lo: val }
+    }
+
+    /// Checked addition
+    pub fn checked_add(self, rhs: U256) -> Option<U256> {
+        let (lo, carry) = self.lo.overflowing_add(rhs.lo);
+        let hi = self.hi.checked_add(rhs.hi)?.checked_add(carry as u128)?;
+        Some(U256 { hi, lo })
+    }
+
+    /// Checked subtraction
+    pub fn checked_sub(self, rhs: U256) -> Option<U256> {
+        let (lo, borrow) = self.lo.overflowing_sub(rhs.lo);
+        let hi = self.hi.checked_sub(rhs.hi)?.checked_sub(borrow as u128)?;
+        Some(U256 { hi, lo })
+    }
+
+    /// Checked widening multiplication of two `u128` values
+    fn checked_mul128(lhs: u128, rhs: u128) -> Option<U256> {
+        let lhs_hi = lhs >> 64;
+        let lhs_lo = lhs & u64::MAX as u128;
+        let rhs_hi = rhs >> 64;
+        let rhs_lo = rhs & u64::MAX as u128;
+
+        let lo_lo = lhs_lo * rhs_lo;
+        let hi_lo = lhs_hi * rhs_lo;
+        let lo_hi = lhs_lo * rhs_hi;
+        let hi_hi = lhs_hi * rhs_hi;
+
+        // `hi_lo + lo_hi` can itself carry out of 128 bits (e.g. when both
+        // operands are near `u128::MAX`), so track that overflow explicitly
+        // instead of treating it as a failure
+        let (cross, cross_overflow) = hi_lo.overflowing_add(lo_hi);
+        let (lo, lo_carry) = (cross << 64).overflowing_add(lo_lo);
+        let hi = hi_hi
+            .checked_add(cross >> 64)?
+            .checked_add(lo_carry as u128)?
+            .checked_add((cross_overflow as u128) << 64)?;
+
+        Some(U256 { hi, lo })
+    }
+
+    /// Multiply this value by a `u128`, checked for overflow within 256 bits
+    pub fn checked_mul(self, rhs: u128) -> Option<U256> {
+        let low = Self::checked_mul128(self.lo, rhs)?;
+        let high = self.hi.checked_mul(rhs)?;
+        low.checked_add(U256 { hi: high, lo: 0 })
+    }
+
+    /// Checked division by a `u128` divisor, using binary long division
+    pub fn checked_div(self, divisor: u128) -> Option<U256> {
+        if divisor == 0 {
+            return None;
+        }
+        let divisor = U256::from_u128(divisor);
+        let mut quotient = U256::ZERO;
+        let mut remainder = U256::ZERO;
+        for i in (0..256).rev() {
+            remainder = remainder.shl1();
+            if self.bit(i) {
+                remainder.lo |= 1;
+            }
+            if remainder >= divisor {
+                remainder = remainder.checked_sub(divisor)?;
+                quotient = quotient.with_bit(i);
+            }
+        }
+        Some(quotient)
+    }
+
+    /// Narrow back down to `u128`, erroring if any high bits are set
+    pub fn to_u128(self) -> Result<u128, SwapError> {
+        if self.hi == 0 {
+            Ok(self.lo)
+        } else {
+            Err(SwapError::ConversionFailure)
+        }
+    }
+
+    /// Checked integer square root, via Newton's method: start from
+    /// `x = (n >> 1) + 1` and iterate `x = (x + n / x) / 2` until it stops
+    /// decreasing. Returns `None` if the root can't be represented in a
+    /// `u128`, which never happens for the product of two realistic token
+    /// reserves.
+    pub fn checked_isqrt(self) -> Option<u128> {
+        if self == U256::ZERO {
+            return Some(0);
+        }
+        // `n >> 1` may not fit in a `u128` even when the root itself does, so
+        // clamp the starting guess to `u128::MAX` in that case instead of
+        // narrowing it directly
+        let mut x = if self.hi == 0 {
+            self.lo.checked_div(2)?.checked_add(1)?
+        } else {
+            u128::MAX
+        };
+        loop {
+            let next = self
+                .checked_div(x)?
+                .checked_add(U256::from_u128(x))?
+                .checked_div(2)?
+                .to_u128()
+                // A step past `u128::MAX` is above any `x`, so it ends the loop
+                .unwrap_or(u128::MAX);
+            if next >= x {
+                return Some(x);
+            }
+            x = next;
+        }
+    }
+
+    fn bit(&self, i: u32) -> bool {
+        if i >= 128 {
+            (self.hi >> (i - 128)) & 1 == 1
+        } else {
+            (self.lo >> i) & 1 == 1
+        }
+    }
+
+    fn with_bit(mut self, i: u32) -> Self {
+        if i >= 128 {
+            self.hi |= 1 << (i - 128);
+        } else {
+            self.lo |= 1 << i;
+        }
+        self
+    }
+
+    fn shl1(self) -> Self {
+        let carry = self.lo >> 127;
+        Self {
+            hi: (self.hi << 1) | carry,
+            lo: self.lo << 1,
+        }
+    }
+}
+
+impl From<u128> for U256 {
+    fn from(val: u128) -> Self {
+        U256::from_u128(val)
+    }
+}
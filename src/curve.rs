@@ -1,6 +1,15 @@
 //! Swap calculations and curve implementations
 
-use crate::{error::SwapError, helpers::to_u128};
+use crate::{
+    bn::U256,
+    error::SwapError,
+    helpers::{checked_div_round, scale_amount, to_u128, to_u64, RoundDirection},
+};
+
+/// The largest reserve amount a single token side of the pool is expected to
+/// hold; used as the upper bound when testing the Newton iterations for
+/// overflow
+pub const MAX_TOKENS_IN: u64 = u64::MAX >> 4;
 
 /// Encodes all results of swapping from a source token to a destination token
 pub struct SwapResult {
@@ -10,55 +19,186 @@ pub struct SwapResult {
     pub new_destination_amount: u128,
     /// Amount of destination token swapped
     pub amount_swapped: u128,
+    /// Total fee taken from the trade, including the admin's cut
+    pub fee: u128,
+    /// Portion of `fee` that leaves the pool for the admin, rather than
+    /// staying in reserves for LPs
+    pub admin_fee: u128,
 }
 
+/// Minimum ramp duration, in seconds
+pub const MIN_RAMP_DURATION: i64 = 86_400;
+/// Minimum amplification coefficient
+pub const MIN_AMP: u128 = 1;
+/// Maximum amplification coefficient
+pub const MAX_AMP: u128 = 1_000_000;
+
 /// The StableSwap invariant calculator.
+///
+/// The amplification coefficient (A) is ramped linearly from
+/// `initial_amp_factor` to `target_amp_factor` over the window
+/// `[start_ramp_ts, stop_ramp_ts]`, rather than jumping instantly, so pool
+/// operators can migrate A without creating an arbitrage shock.
+#[derive(Debug)]
 pub struct StableSwap {
-    /// Amplification coefficient (A)
-    pub amp_factor: u128,
+    /// Amplification coefficient (A) at the start of the ramp
+    pub initial_amp_factor: u128,
+    /// Amplification coefficient (A) at the end of the ramp
+    pub target_amp_factor: u128,
+    /// Timestamp when the ramp begins
+    pub start_ramp_ts: i64,
+    /// Timestamp when the ramp ends
+    pub stop_ramp_ts: i64,
 }
 
 impl StableSwap {
-    /// New StableSwap calculator
-    pub fn new(amp_factor_u64: u64) -> Result<StableSwap, SwapError> {
-        let amp_factor = to_u128(amp_factor_u64)?;
-        Ok(Self { amp_factor })
+    /// New StableSwap calculator, validating the amplification bounds and
+    /// ramp duration
+    pub fn new(
+        initial_amp_factor: u64,
+        target_amp_factor: u64,
+        start_ramp_ts: i64,
+        stop_ramp_ts: i64,
+    ) -> Result<StableSwap, SwapError> {
+        let initial_amp_factor = to_u128(initial_amp_factor)?;
+        let target_amp_factor = to_u128(target_amp_factor)?;
+        if !(MIN_AMP..=MAX_AMP).contains(&initial_amp_factor)
+            || !(MIN_AMP..=MAX_AMP).contains(&target_amp_factor)
+        {
+            return Err(SwapError::InvalidInput);
+        }
+        if stop_ramp_ts - start_ramp_ts < MIN_RAMP_DURATION {
+            return Err(SwapError::InvalidInput);
+        }
+
+        Ok(Self {
+            initial_amp_factor,
+            target_amp_factor,
+            start_ramp_ts,
+            stop_ramp_ts,
+        })
+    }
+
+    /// Compute the amplification coefficient in effect at `current_ts`,
+    /// linearly interpolating between `initial_amp_factor` and
+    /// `target_amp_factor` over the ramp window
+    pub fn compute_amp_factor(&self, current_ts: i64) -> Result<u128, SwapError> {
+        if current_ts <= self.start_ramp_ts {
+            return Ok(self.initial_amp_factor);
+        }
+        if current_ts >= self.stop_ramp_ts {
+            return Ok(self.target_amp_factor);
+        }
+
+        let time_range = to_u128((self.stop_ramp_ts - self.start_ramp_ts) as u64)?;
+        let time_delta = to_u128((current_ts - self.start_ramp_ts) as u64)?;
+
+        if self.target_amp_factor >= self.initial_amp_factor {
+            let amp_range = self.target_amp_factor - self.initial_amp_factor;
+            let amp_delta = amp_range
+                .checked_mul(time_delta)
+                .ok_or(SwapError::CalculationFailure)?
+                .checked_div(time_range)
+                .ok_or(SwapError::CalculationFailure)?;
+            Ok(self.initial_amp_factor + amp_delta)
+        } else {
+            let amp_range = self.initial_amp_factor - self.target_amp_factor;
+            let amp_delta = amp_range
+                .checked_mul(time_delta)
+                .ok_or(SwapError::CalculationFailure)?
+                .checked_div(time_range)
+                .ok_or(SwapError::CalculationFailure)?;
+            Ok(self.initial_amp_factor - amp_delta)
+        }
     }
 
     /// Compute stable swap invariant (D)
     /// Equation:
     /// A * sum(x_i) * n**n + D = A * D * n**n + D**(n+1) / (n**n * prod(x_i))
-    pub fn compute_d(&self, amount_a: u128, amount_b: u128) -> u128 {
-        // XXX: Curve uses u256
-        // TODO: Handle overflows
+    ///
+    /// The products involving powers of D (`D_P` and the numerator of each
+    /// Newton step) run through `U256` so that realistic reserve sizes (up to
+    /// `MAX_TOKENS_IN`) never overflow; the smaller products (`A * n`, the
+    /// step's divisor) use checked `u128` math, and D itself is narrowed to
+    /// `u128` with a checked conversion wherever it is used as a multiplier.
+    pub fn compute_d(
+        &self,
+        current_ts: i64,
+        amount_a: u128,
+        amount_b: u128,
+    ) -> Result<u128, SwapError> {
         let n_coins: u128 = 2; // n
-        let sum_x = amount_a + amount_b; // sum(x_i), a.k.a S
+        let sum_x = amount_a
+            .checked_add(amount_b)
+            .ok_or(SwapError::CalculationFailure)?; // sum(x_i), a.k.a S
         if sum_x == 0 {
-            0
+            Ok(0)
         } else {
-            let mut d_prev: u128;
-            let mut d = sum_x;
-            let leverage = self.amp_factor * n_coins; // A * n
+            let mut d_prev: U256;
+            let mut d = U256::from_u128(sum_x);
+            let leverage = self
+                .compute_amp_factor(current_ts)?
+                .checked_mul(n_coins)
+                .ok_or(SwapError::CalculationFailure)?; // A * n
+            let amount_a_n = amount_a
+                .checked_mul(n_coins)
+                .ok_or(SwapError::CalculationFailure)?;
+            let amount_b_n = amount_b
+                .checked_mul(n_coins)
+                .ok_or(SwapError::CalculationFailure)?;
 
             // Newton's method to approximate D
             for _ in 0..128 {
                 let mut d_p = d;
-                d_p = d_p * d / (amount_a * n_coins);
-                d_p = d_p * d / (amount_b * n_coins);
+                d_p = d_p
+                    .checked_mul(d.to_u128()?)
+                    .ok_or(SwapError::CalculationFailure)?
+                    .checked_div(amount_a_n)
+                    .ok_or(SwapError::CalculationFailure)?;
+                d_p = d_p
+                    .checked_mul(d.to_u128()?)
+                    .ok_or(SwapError::CalculationFailure)?
+                    .checked_div(amount_b_n)
+                    .ok_or(SwapError::CalculationFailure)?;
                 d_prev = d;
-                d = (leverage * sum_x + d_p * n_coins) * d
-                    / ((leverage - 1) * d + (n_coins + 1) * d_p);
+                let divisor = leverage
+                    .checked_sub(1)
+                    .ok_or(SwapError::CalculationFailure)?
+                    .checked_mul(d.to_u128()?)
+                    .ok_or(SwapError::CalculationFailure)?
+                    .checked_add(
+                        (n_coins + 1)
+                            .checked_mul(d_p.to_u128()?)
+                            .ok_or(SwapError::CalculationFailure)?,
+                    )
+                    .ok_or(SwapError::CalculationFailure)?;
+                d = U256::from_u128(sum_x)
+                    .checked_mul(leverage)
+                    .ok_or(SwapError::CalculationFailure)?
+                    .checked_add(
+                        d_p.checked_mul(n_coins)
+                            .ok_or(SwapError::CalculationFailure)?,
+                    )
+                    .ok_or(SwapError::CalculationFailure)?
+                    .checked_mul(d.to_u128()?)
+                    .ok_or(SwapError::CalculationFailure)?
+                    .checked_div(divisor)
+                    .ok_or(SwapError::CalculationFailure)?;
                 // Equality with the precision of 1
                 if d > d_p {
-                    if d - d_prev <= 1 {
+                    if d.checked_sub(d_prev).ok_or(SwapError::CalculationFailure)?
+                        <= U256::from_u128(1)
+                    {
                         break;
                     }
-                } else if d_prev - d <= 1 {
+                } else if d_prev.checked_sub(d).ok_or(SwapError::CalculationFailure)?
+                    <= U256::from_u128(1)
+                {
                     break;
                 }
             }
 
-            d
+            d.to_u128()
         }
     }
 
@@ -66,98 +206,492 @@ impl StableSwap {
     /// Solve for y:
     /// y**2 + y * (sum' - (A*n**n - 1) * D / (A * n**n)) = D ** (n + 1) / (n ** (2 * n) * prod' * A)
     /// y**2 + b*y = c
-    pub fn compute_y(&self, x: u128, d: u128) -> u128 {
-        // XXX: Curve uses u256
-        // TODO: Handle overflows
-        let n_coins = 2;
-        let leverage = self.amp_factor * n_coins; // A * n
+    ///
+    /// As in `compute_d`, `d * d * d` and `y * y` are carried in `U256`; `b`
+    /// and the divisor of `c` use checked `u128` math, and `y` is narrowed to
+    /// `u128` with a checked conversion wherever it is used as a multiplier.
+    pub fn compute_y(&self, current_ts: i64, x: u128, d: u128) -> Result<u128, SwapError> {
+        let n_coins: u128 = 2;
+        let leverage = self
+            .compute_amp_factor(current_ts)?
+            .checked_mul(n_coins)
+            .ok_or(SwapError::CalculationFailure)?; // A * n
 
         // sum' = prod' = x
         // c =  D ** (n + 1) / (n ** (2 * n) * prod' * A)
-        let c = d * d * d / (x * n_coins * n_coins * leverage);
+        let c_divisor = x
+            .checked_mul(n_coins)
+            .ok_or(SwapError::CalculationFailure)?
+            .checked_mul(n_coins)
+            .ok_or(SwapError::CalculationFailure)?
+            .checked_mul(leverage)
+            .ok_or(SwapError::CalculationFailure)?;
+        let c = U256::from_u128(d)
+            .checked_mul(d)
+            .ok_or(SwapError::CalculationFailure)?
+            .checked_mul(d)
+            .ok_or(SwapError::CalculationFailure)?
+            .checked_div(c_divisor)
+            .ok_or(SwapError::CalculationFailure)?;
         // b = sum' - (A*n**n - 1) * D / (A * n**n)
-        let b = x + d / leverage; // d is subtracted on line 82
+        let b = x
+            .checked_add(
+                d.checked_div(leverage)
+                    .ok_or(SwapError::CalculationFailure)?,
+            )
+            .ok_or(SwapError::CalculationFailure)?; // d is subtracted below
 
         // Solve for y by approximating: y**2 + b*y = c
-        let mut y_prev: u128;
-        let mut y = d;
+        let mut y_prev: U256;
+        let mut y = U256::from_u128(d);
         for _ in 0..128 {
             y_prev = y;
-            y = (y * y + c) / (2 * y + b - d);
+            y = y
+                .checked_mul(y.to_u128()?)
+                .ok_or(SwapError::CalculationFailure)?
+                .checked_add(c)
+                .ok_or(SwapError::CalculationFailure)?
+                .checked_div(2 * y.to_u128()? + b - d)
+                .ok_or(SwapError::CalculationFailure)?;
             if y > y_prev {
-                if y - y_prev <= 1 {
+                if y.checked_sub(y_prev).ok_or(SwapError::CalculationFailure)? <= U256::from_u128(1)
+                {
                     break;
                 }
-            } else if y_prev - y <= 1 {
+            } else if y_prev.checked_sub(y).ok_or(SwapError::CalculationFailure)?
+                <= U256::from_u128(1)
+            {
                 break;
             }
         }
 
-        y
+        y.to_u128()
     }
 
     /// Calcuate withdrawal amount when withdrawing only one type of token
     /// Calculation:
     /// 1. Get current D
     /// 2. Solve Eqn against y_i for D - _token_amount
+    ///
+    /// `round_direction` governs the D attributed to the burned pool tokens:
+    /// `Floor` favors the pool (the burned share rounds down, leaving more D,
+    /// and therefore more value, with the pool), `Ceiling` favors the
+    /// withdrawer. The fee taken out of the post-withdrawal reserves always
+    /// rounds up so it never leaks value out of the pool, and the final
+    /// payout always rounds down.
+    ///
+    /// The total fee is split the same way as in `swap_to`: the admin's cut
+    /// leaves the pool entirely, while the rest stays in reserves for LPs.
+    /// Returns `(dy, lp_fee, admin_fee)`.
+    #[allow(clippy::too_many_arguments)]
     pub fn compute_withdraw_one(
         &self,
+        current_ts: i64,
         pool_token_amount: u64,
         pool_token_supply: u64,
         swap_base_amount: u64,  // Same denomination of token to be withdrawn
         swap_quote_amount: u64, // Counter denomination of token to be withdrawn
         fee_numerator: u64,
         fee_denominator: u64,
-    ) -> (u64, u64) {
-        // XXX: Curve uses u256
-        // TODO: Handle overflows
+        admin_fee_numerator: u64,
+        admin_fee_denominator: u64,
+        round_direction: RoundDirection,
+    ) -> Result<(u64, u64, u64), SwapError> {
         let n_coins = 2;
-        let d_0 = self.compute_d(swap_base_amount, swap_quote_amount);
-        let d_1 = d_0 - pool_token_amount * d_0 / pool_token_supply;
-        let new_y = self.compute_y(swap_quote_amount, d_1);
+        let pool_token_amount = to_u128(pool_token_amount)?;
+        let pool_token_supply = to_u128(pool_token_supply)?;
+        let swap_base_amount = to_u128(swap_base_amount)?;
+        let swap_quote_amount = to_u128(swap_quote_amount)?;
+        let fee_numerator = to_u128(fee_numerator)?;
+        let fee_denominator = to_u128(fee_denominator)?;
+        let admin_fee_numerator = to_u128(admin_fee_numerator)?;
+        let admin_fee_denominator = to_u128(admin_fee_denominator)?;
+
+        let d_0 = self.compute_d(current_ts, swap_base_amount, swap_quote_amount)?;
+        let d_1 = d_0
+            - checked_div_round(pool_token_amount * d_0, pool_token_supply, round_direction)
+                .ok_or(SwapError::CalculationFailure)?;
+        let new_y = self.compute_y(current_ts, swap_quote_amount, d_1)?;
 
         let fee = fee_numerator * n_coins / (4 * (n_coins - 1)); // XXX: Why divide by 4?
         let expected_base_amount = swap_base_amount * d_1 / d_0 - new_y;
         let expected_quote_amount = swap_quote_amount - swap_quote_amount * d_1 / d_0;
-        let new_base_amount = swap_base_amount - expected_base_amount * fee / fee_denominator;
-        let new_quote_amount = swap_quote_amount - expected_quote_amount * fee / fee_denominator;
+        let new_base_amount = swap_base_amount
+            - checked_div_round(
+                expected_base_amount * fee,
+                fee_denominator,
+                RoundDirection::Ceiling,
+            )
+            .ok_or(SwapError::CalculationFailure)?;
+        let new_quote_amount = swap_quote_amount
+            - checked_div_round(
+                expected_quote_amount * fee,
+                fee_denominator,
+                RoundDirection::Ceiling,
+            )
+            .ok_or(SwapError::CalculationFailure)?;
 
-        let dy = new_base_amount - self.compute_y(new_quote_amount, d_1);
+        let dy = new_base_amount - self.compute_y(current_ts, new_quote_amount, d_1)?;
         let dy_0 = swap_base_amount - new_y;
+        let total_fee = dy_0 - dy;
+        let admin_fee = admin_fee_numerator
+            .checked_mul(total_fee)
+            .ok_or(SwapError::CalculationFailure)?
+            .checked_div(admin_fee_denominator)
+            .ok_or(SwapError::CalculationFailure)?;
+        let lp_fee = total_fee - admin_fee;
 
-        (dy, dy_0 - dy)
+        Ok((to_u64(dy)?, to_u64(lp_fee)?, to_u64(admin_fee)?))
     }
 
     /// Compute SwapResult after an exchange
+    ///
+    /// The trade fee is split in two: the admin's cut (`admin_fee_numerator`
+    /// / `admin_fee_denominator` of the trade fee) leaves the pool entirely,
+    /// while the rest stays in reserves to reward LPs.
+    #[allow(clippy::too_many_arguments)]
     pub fn swap_to(
         &self,
+        current_ts: i64,
         source_amount: u128,
         swap_source_amount: u128,
         swap_destination_amount: u128,
         fee_numerator: u128,
         fee_denominator: u128,
+        admin_fee_numerator: u128,
+        admin_fee_denominator: u128,
     ) -> Option<SwapResult> {
-        let y = self.compute_y(
-            swap_source_amount + source_amount,
-            self.compute_d(swap_source_amount, swap_destination_amount),
-        );
+        let d = self
+            .compute_d(current_ts, swap_source_amount, swap_destination_amount)
+            .ok()?;
+        let y = self
+            .compute_y(current_ts, swap_source_amount + source_amount, d)
+            .ok()?;
         let dy = swap_destination_amount.checked_sub(y)?;
-        let dy_fee = dy
+        let fee = dy
             .checked_mul(fee_numerator)?
             .checked_div(fee_denominator)?;
+        let admin_fee = admin_fee_numerator
+            .checked_mul(fee)?
+            .checked_div(admin_fee_denominator)?;
 
-        let amount_swapped = dy - dy_fee;
-        let new_destination_amount = swap_destination_amount.checked_sub(amount_swapped)?;
+        let amount_swapped = dy.checked_sub(fee)?;
+        let new_destination_amount = swap_destination_amount
+            .checked_sub(amount_swapped)?
+            .checked_sub(admin_fee)?;
         let new_source_amount = swap_source_amount.checked_add(source_amount)?;
 
         Some(SwapResult {
             new_source_amount,
             new_destination_amount,
             amount_swapped,
+            fee,
+            admin_fee,
         })
     }
 }
 
+/// A common interface for the different swap curve shapes a pool can be
+/// parameterized with, so a single pool program isn't hard-coded to one
+/// AMM's math
+pub trait SwapCalculator: std::fmt::Debug {
+    /// Compute the result of swapping `source_amount` of the source token.
+    ///
+    /// The trade fee is split in two: the admin's cut (`admin_fee_numerator`
+    /// / `admin_fee_denominator` of the trade fee) leaves the pool entirely,
+    /// while the rest stays in reserves to reward LPs.
+    #[allow(clippy::too_many_arguments)]
+    fn compute_swap(
+        &self,
+        current_ts: i64,
+        source_amount: u128,
+        swap_source_amount: u128,
+        swap_destination_amount: u128,
+        fee_numerator: u128,
+        fee_denominator: u128,
+        admin_fee_numerator: u128,
+        admin_fee_denominator: u128,
+    ) -> Option<SwapResult>;
+
+    /// Compute how many pool tokens a deposit of `source_amount` into the
+    /// `swap_source_amount` side of the pool should mint
+    fn trading_tokens_to_pool_tokens(
+        &self,
+        source_amount: u128,
+        swap_source_amount: u128,
+        pool_supply: u128,
+        round_direction: RoundDirection,
+    ) -> Option<u128> {
+        scale_amount(
+            source_amount,
+            pool_supply,
+            swap_source_amount,
+            round_direction,
+        )
+    }
+
+    /// Compute how much of each trading token a withdrawal of `pool_tokens`
+    /// is owed, proportional to the pool's current reserves
+    fn pool_tokens_to_trading_tokens(
+        &self,
+        pool_tokens: u128,
+        pool_token_supply: u128,
+        swap_token_a_amount: u128,
+        swap_token_b_amount: u128,
+        round_direction: RoundDirection,
+    ) -> Option<(u128, u128)> {
+        let token_a_amount = scale_amount(
+            pool_tokens,
+            swap_token_a_amount,
+            pool_token_supply,
+            round_direction,
+        )?;
+        let token_b_amount = scale_amount(
+            pool_tokens,
+            swap_token_b_amount,
+            pool_token_supply,
+            round_direction,
+        )?;
+        Some((token_a_amount, token_b_amount))
+    }
+}
+
+impl SwapCalculator for StableSwap {
+    fn compute_swap(
+        &self,
+        current_ts: i64,
+        source_amount: u128,
+        swap_source_amount: u128,
+        swap_destination_amount: u128,
+        fee_numerator: u128,
+        fee_denominator: u128,
+        admin_fee_numerator: u128,
+        admin_fee_denominator: u128,
+    ) -> Option<SwapResult> {
+        self.swap_to(
+            current_ts,
+            source_amount,
+            swap_source_amount,
+            swap_destination_amount,
+            fee_numerator,
+            fee_denominator,
+            admin_fee_numerator,
+            admin_fee_denominator,
+        )
+    }
+}
+
+/// A Uniswap-style constant-product (`x * y = k`) curve
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ConstantProductCurve;
+
+impl SwapCalculator for ConstantProductCurve {
+    fn compute_swap(
+        &self,
+        _current_ts: i64,
+        source_amount: u128,
+        swap_source_amount: u128,
+        swap_destination_amount: u128,
+        fee_numerator: u128,
+        fee_denominator: u128,
+        admin_fee_numerator: u128,
+        admin_fee_denominator: u128,
+    ) -> Option<SwapResult> {
+        let new_source_amount = swap_source_amount.checked_add(source_amount)?;
+        let invariant = swap_source_amount.checked_mul(swap_destination_amount)?;
+        // Ceiling so any rounding on the output side favors the pool
+        let new_destination_amount =
+            checked_div_round(invariant, new_source_amount, RoundDirection::Ceiling)?;
+        let destination_amount_swapped =
+            swap_destination_amount.checked_sub(new_destination_amount)?;
+
+        let fee = destination_amount_swapped
+            .checked_mul(fee_numerator)?
+            .checked_div(fee_denominator)?;
+        let admin_fee = admin_fee_numerator
+            .checked_mul(fee)?
+            .checked_div(admin_fee_denominator)?;
+
+        let amount_swapped = destination_amount_swapped.checked_sub(fee)?;
+        let new_destination_amount = new_destination_amount
+            .checked_add(fee)?
+            .checked_sub(admin_fee)?;
+
+        Some(SwapResult {
+            new_source_amount,
+            new_destination_amount,
+            amount_swapped,
+            fee,
+            admin_fee,
+        })
+    }
+}
+
+/// A curve that always swaps at a constant 1:1 price, for pairs of pegged
+/// assets that aren't expected to diverge
+#[derive(Clone, Copy, Debug, Default)]
+pub struct FlatCurve;
+
+impl SwapCalculator for FlatCurve {
+    fn compute_swap(
+        &self,
+        _current_ts: i64,
+        source_amount: u128,
+        swap_source_amount: u128,
+        swap_destination_amount: u128,
+        fee_numerator: u128,
+        fee_denominator: u128,
+        admin_fee_numerator: u128,
+        admin_fee_denominator: u128,
+    ) -> Option<SwapResult> {
+        let fee = source_amount
+            .checked_mul(fee_numerator)?
+            .checked_div(fee_denominator)?;
+        let admin_fee = admin_fee_numerator
+            .checked_mul(fee)?
+            .checked_div(admin_fee_denominator)?;
+
+        let amount_swapped = source_amount.checked_sub(fee)?;
+        let new_destination_amount = swap_destination_amount
+            .checked_sub(amount_swapped)?
+            .checked_sub(admin_fee)?;
+        let new_source_amount = swap_source_amount.checked_add(source_amount)?;
+
+        Some(SwapResult {
+            new_source_amount,
+            new_destination_amount,
+            amount_swapped,
+            fee,
+            admin_fee,
+        })
+    }
+}
+
+/// Identifies which swap curve a pool is parameterized with, along with
+/// whatever parameters that curve needs to build its calculator
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum CurveType {
+    /// The StableSwap invariant, for pools of assets expected to trade near
+    /// parity
+    Stable {
+        /// Amplification coefficient (A) at the start of the ramp
+        initial_amp_factor: u64,
+        /// Amplification coefficient (A) at the end of the ramp
+        target_amp_factor: u64,
+        /// Timestamp when the ramp begins
+        start_ramp_ts: i64,
+        /// Timestamp when the ramp ends
+        stop_ramp_ts: i64,
+    },
+    /// The Uniswap-style constant-product invariant
+    ConstantProduct,
+    /// The constant-price invariant, for pegged assets
+    Flat,
+}
+
+/// Wraps a curve calculator together with the `CurveType` it was built from,
+/// so a pool can be parameterized by curve at init time instead of hard
+/// depending on `StableSwap`
+#[derive(Debug)]
+pub struct SwapCurve {
+    /// Identifies which calculator this pool is using
+    curve_type: CurveType,
+    /// The curve calculator itself
+    calculator: Box<dyn SwapCalculator>,
+}
+
+impl SwapCurve {
+    /// Build the calculator matching `curve_type` and pair it with the tag,
+    /// so the two can never drift apart the way they could if a caller
+    /// built `SwapCurve`'s fields by hand
+    pub fn new(curve_type: CurveType) -> Result<SwapCurve, SwapError> {
+        let calculator: Box<dyn SwapCalculator> = match curve_type {
+            CurveType::Stable {
+                initial_amp_factor,
+                target_amp_factor,
+                start_ramp_ts,
+                stop_ramp_ts,
+            } => Box::new(StableSwap::new(
+                initial_amp_factor,
+                target_amp_factor,
+                start_ramp_ts,
+                stop_ramp_ts,
+            )?),
+            CurveType::ConstantProduct => Box::new(ConstantProductCurve),
+            CurveType::Flat => Box::new(FlatCurve),
+        };
+        Ok(SwapCurve {
+            curve_type,
+            calculator,
+        })
+    }
+
+    /// Which curve this pool is using
+    pub fn curve_type(&self) -> CurveType {
+        self.curve_type
+    }
+
+    /// Compute the result of swapping `source_amount` of the source token
+    #[allow(clippy::too_many_arguments)]
+    pub fn compute_swap(
+        &self,
+        current_ts: i64,
+        source_amount: u128,
+        swap_source_amount: u128,
+        swap_destination_amount: u128,
+        fee_numerator: u128,
+        fee_denominator: u128,
+        admin_fee_numerator: u128,
+        admin_fee_denominator: u128,
+    ) -> Option<SwapResult> {
+        self.calculator.compute_swap(
+            current_ts,
+            source_amount,
+            swap_source_amount,
+            swap_destination_amount,
+            fee_numerator,
+            fee_denominator,
+            admin_fee_numerator,
+            admin_fee_denominator,
+        )
+    }
+
+    /// Compute how many pool tokens a deposit of `source_amount` should mint
+    pub fn trading_tokens_to_pool_tokens(
+        &self,
+        source_amount: u128,
+        swap_source_amount: u128,
+        pool_supply: u128,
+        round_direction: RoundDirection,
+    ) -> Option<u128> {
+        self.calculator.trading_tokens_to_pool_tokens(
+            source_amount,
+            swap_source_amount,
+            pool_supply,
+            round_direction,
+        )
+    }
+
+    /// Compute how much of each trading token a withdrawal of `pool_tokens`
+    /// is owed
+    pub fn pool_tokens_to_trading_tokens(
+        &self,
+        pool_tokens: u128,
+        pool_token_supply: u128,
+        swap_token_a_amount: u128,
+        swap_token_b_amount: u128,
+        round_direction: RoundDirection,
+    ) -> Option<(u128, u128)> {
+        self.calculator.pool_tokens_to_trading_tokens(
+            pool_tokens,
+            pool_token_supply,
+            swap_token_a_amount,
+            swap_token_b_amount,
+            round_direction,
+        )
+    }
+}
+
 /// Conversions for pool tokens, how much to deposit / withdraw, along with
 /// proper initialization
 pub struct PoolTokenConverter {
@@ -179,18 +713,32 @@ impl PoolTokenConverter {
         }
     }
 
-    /// A tokens for pool tokens
-    pub fn token_a_rate(&self, pool_tokens: u128) -> Option<u128> {
-        pool_tokens
-            .checked_mul(self.token_a)?
-            .checked_div(self.supply)
+    /// Mint the initial pool token supply for a pool's very first deposit, as
+    /// the integer geometric mean of the two reserves (Uniswap-style), so
+    /// that neither token's weight dominates the starting supply. Callers
+    /// should use this instead of `token_a_rate`/`token_b_rate` whenever
+    /// `supply == 0`, since there are no existing pool tokens to price
+    /// against yet.
+    pub fn initial_pool_tokens(token_a: u128, token_b: u128) -> Option<u128> {
+        U256::from_u128(token_a)
+            .checked_mul(token_b)?
+            .checked_isqrt()
     }
 
-    /// B tokens for pool tokens
-    pub fn token_b_rate(&self, pool_tokens: u128) -> Option<u128> {
-        pool_tokens
-            .checked_mul(self.token_b)?
-            .checked_div(self.supply)
+    /// A tokens for pool tokens. Round `Ceiling` for amounts owed to the
+    /// pool (e.g. a deposit) and `Floor` for amounts paid out (e.g. a
+    /// withdrawal), so value never leaks out of the pool on truncation.
+    /// Returns `None` when `supply == 0`; use `initial_pool_tokens` instead.
+    pub fn token_a_rate(&self, pool_tokens: u128, round_direction: RoundDirection) -> Option<u128> {
+        scale_amount(pool_tokens, self.token_a, self.supply, round_direction)
+    }
+
+    /// B tokens for pool tokens. Round `Ceiling` for amounts owed to the
+    /// pool (e.g. a deposit) and `Floor` for amounts paid out (e.g. a
+    /// withdrawal), so value never leaks out of the pool on truncation.
+    /// Returns `None` when `supply == 0`; use `initial_pool_tokens` instead.
+    pub fn token_b_rate(&self, pool_tokens: u128, round_direction: RoundDirection) -> Option<u128> {
+        scale_amount(pool_tokens, self.token_b, self.supply, round_direction)
     }
 }
 
@@ -200,6 +748,70 @@ mod tests {
     use rand::Rng;
     use sim::{Model, MODEL_FEE_DENOMINATOR, MODEL_FEE_NUMERATOR};
 
+    /// A timestamp used by tests that don't care about ramping; any value at
+    /// or past `stop_ramp_ts` works since `fixed_swap` sets initial and
+    /// target amp factors equal
+    const CURRENT_TS: i64 = MIN_RAMP_DURATION;
+
+    /// A `StableSwap` with no ramp in progress, for tests that only care
+    /// about a constant amplification coefficient
+    fn fixed_swap(amp_factor: u128) -> StableSwap {
+        StableSwap {
+            initial_amp_factor: amp_factor,
+            target_amp_factor: amp_factor,
+            start_ramp_ts: 0,
+            stop_ramp_ts: MIN_RAMP_DURATION,
+        }
+    }
+
+    #[test]
+    fn test_ramp_amp_factor() {
+        let swap = StableSwap {
+            initial_amp_factor: 100,
+            target_amp_factor: 1000,
+            start_ramp_ts: 0,
+            stop_ramp_ts: MIN_RAMP_DURATION,
+        };
+
+        assert_eq!(swap.compute_amp_factor(-1).unwrap(), 100);
+        assert_eq!(swap.compute_amp_factor(0).unwrap(), 100);
+        assert_eq!(swap.compute_amp_factor(MIN_RAMP_DURATION / 2).unwrap(), 550);
+        assert_eq!(swap.compute_amp_factor(MIN_RAMP_DURATION).unwrap(), 1000);
+        assert_eq!(
+            swap.compute_amp_factor(MIN_RAMP_DURATION * 2).unwrap(),
+            1000
+        );
+
+        // Ramping down should interpolate the same way, in reverse
+        let ramp_down = StableSwap {
+            initial_amp_factor: 1000,
+            target_amp_factor: 100,
+            start_ramp_ts: 0,
+            stop_ramp_ts: MIN_RAMP_DURATION,
+        };
+        assert_eq!(
+            ramp_down.compute_amp_factor(MIN_RAMP_DURATION / 2).unwrap(),
+            550
+        );
+    }
+
+    #[test]
+    fn test_new_validates_amp_and_ramp_duration() {
+        assert_eq!(
+            StableSwap::new(0, 100, 0, MIN_RAMP_DURATION).unwrap_err(),
+            SwapError::InvalidInput
+        );
+        assert_eq!(
+            StableSwap::new(100, MAX_AMP as u64 + 1, 0, MIN_RAMP_DURATION).unwrap_err(),
+            SwapError::InvalidInput
+        );
+        assert_eq!(
+            StableSwap::new(100, 200, 0, MIN_RAMP_DURATION - 1).unwrap_err(),
+            SwapError::InvalidInput
+        );
+        assert!(StableSwap::new(100, 200, 0, MIN_RAMP_DURATION).is_ok());
+    }
+
     fn check_pool_token_a_rate(
         token_a: u128,
         token_b: u128,
@@ -208,7 +820,10 @@ mod tests {
         expected: Option<u128>,
     ) {
         let calculator = PoolTokenConverter::new(supply, token_a, token_b);
-        assert_eq!(calculator.token_a_rate(deposit), expected);
+        assert_eq!(
+            calculator.token_a_rate(deposit, RoundDirection::Floor),
+            expected
+        );
         assert_eq!(calculator.supply, supply);
     }
 
@@ -221,20 +836,73 @@ mod tests {
         check_pool_token_a_rate(u128::MAX, u128::MAX, 5, 10, None);
     }
 
+    #[test]
+    fn test_token_rate_rounding() {
+        // 1 pool token out of a supply of 3, against 10 A tokens, is worth
+        // 10/3 A tokens: the exact rate is not representable, so Ceiling and
+        // Floor must differ
+        let calculator = PoolTokenConverter::new(3, 10, 10);
+        assert_eq!(calculator.token_a_rate(1, RoundDirection::Floor), Some(3));
+        assert_eq!(calculator.token_a_rate(1, RoundDirection::Ceiling), Some(4));
+    }
+
+    #[test]
+    fn test_deposit_then_withdraw_never_returns_more_than_was_put_in() {
+        let calculator = PoolTokenConverter::new(997, 10_007, 20_003);
+        for pool_tokens in [1_u128, 2, 3, 17, 101, 996] {
+            let deposit_amount = calculator
+                .token_a_rate(pool_tokens, RoundDirection::Ceiling)
+                .unwrap();
+            let withdraw_amount = calculator
+                .token_a_rate(pool_tokens, RoundDirection::Floor)
+                .unwrap();
+            assert!(withdraw_amount <= deposit_amount);
+        }
+    }
+
+    #[test]
+    fn test_initial_pool_tokens_is_the_geometric_mean() {
+        // Equal reserves: the geometric mean is exact
+        assert_eq!(PoolTokenConverter::initial_pool_tokens(100, 100), Some(100));
+
+        // Lopsided reserves: isqrt(10 * 1_000) = isqrt(10_000) = 100
+        assert_eq!(
+            PoolTokenConverter::initial_pool_tokens(10, 1_000),
+            Some(100)
+        );
+
+        // A product that doesn't have an exact integer square root truncates
+        // down, same as any other checked division in this crate
+        assert_eq!(PoolTokenConverter::initial_pool_tokens(2, 3), Some(2));
+
+        // Reserves large enough that the product would overflow a u128 are
+        // still handled correctly via the widened U256 math
+        assert_eq!(
+            PoolTokenConverter::initial_pool_tokens(u128::MAX, u128::MAX),
+            Some(u128::MAX)
+        );
+    }
+
+    #[test]
+    fn test_token_rate_returns_none_for_an_empty_pool() {
+        let calculator = PoolTokenConverter::new(0, 10_000, 10_000);
+        assert_eq!(calculator.token_a_rate(1, RoundDirection::Floor), None);
+        assert_eq!(calculator.token_b_rate(1, RoundDirection::Ceiling), None);
+    }
+
     fn check_d(model: &Model, amount_a: u128, amount_b: u128) -> u128 {
-        let swap = StableSwap {
-            amp_factor: model.amp_factor,
-        };
-        let d = swap.compute_d(amount_a, amount_b);
+        let swap = fixed_swap(model.amp_factor);
+        let d = swap.compute_d(CURRENT_TS, amount_a, amount_b).unwrap();
         assert_eq!(d, model.sim_d());
         d
     }
 
     fn check_y(model: &Model, x: u128, d: u128) {
-        let swap = StableSwap {
-            amp_factor: model.amp_factor,
-        };
-        assert_eq!(swap.compute_y(x, d), model.sim_y(0, 1, x))
+        let swap = fixed_swap(model.amp_factor);
+        assert_eq!(
+            swap.compute_y(CURRENT_TS, x, d).unwrap(),
+            model.sim_y(0, 1, x)
+        )
     }
 
     #[test]
@@ -284,6 +952,28 @@ mod tests {
         check_y(&model, rng.gen_range(0, amount_a), d);
     }
 
+    #[test]
+    fn test_curve_math_with_large_reserves_does_not_overflow() {
+        let amount_a = (MAX_TOKENS_IN >> 1) as u128;
+        let amount_b = (MAX_TOKENS_IN >> 1) as u128;
+
+        for amp_factor in [1_u128, 100, 1000, 1_000_000] {
+            let swap = fixed_swap(amp_factor);
+            let d = swap.compute_d(CURRENT_TS, amount_a, amount_b).unwrap();
+            swap.compute_y(CURRENT_TS, amount_a, d).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_curve_math_with_max_tokens_in_does_not_overflow() {
+        let amount_a = MAX_TOKENS_IN as u128;
+        let amount_b = MAX_TOKENS_IN as u128;
+        let swap = fixed_swap(1_000_000);
+
+        let d = swap.compute_d(CURRENT_TS, amount_a, amount_b).unwrap();
+        swap.compute_y(CURRENT_TS, amount_a, d).unwrap();
+    }
+
     fn check_swap(
         amp_factor: u128,
         source_amount: u128,
@@ -291,18 +981,21 @@ mod tests {
         swap_destination_amount: u128,
     ) {
         let n_coin = 2;
-        let swap = StableSwap { amp_factor };
+        let swap = fixed_swap(amp_factor);
         let result = swap
             .swap_to(
+                CURRENT_TS,
                 source_amount,
                 swap_source_amount,
                 swap_destination_amount,
                 MODEL_FEE_NUMERATOR,
                 MODEL_FEE_DENOMINATOR,
+                0,
+                1,
             )
             .unwrap();
         let model = Model::new(
-            swap.amp_factor,
+            swap.initial_amp_factor,
             vec![swap_source_amount, swap_destination_amount],
             n_coin,
         );
@@ -378,6 +1071,192 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_swap_to_admin_fee_leaves_the_pool() {
+        let amp_factor = 100;
+        let source_amount: u128 = 10_000_000_000;
+        let swap_source_amount: u128 = 50_000_000_000;
+        let swap_destination_amount: u128 = 50_000_000_000;
+
+        let swap = fixed_swap(amp_factor);
+        // A quarter of the trade fee goes to the admin and leaves the pool
+        let result = swap
+            .swap_to(
+                CURRENT_TS,
+                source_amount,
+                swap_source_amount,
+                swap_destination_amount,
+                MODEL_FEE_NUMERATOR,
+                MODEL_FEE_DENOMINATOR,
+                1,
+                4,
+            )
+            .unwrap();
+        assert_eq!(result.admin_fee, result.fee / 4);
+
+        // With no fee at all, the destination reserve would be left at
+        // `swap_destination_amount - dy`; any fee kept in reserves for LPs
+        // (as opposed to sent to the admin) shows up as extra amount on top
+        // of that baseline
+        let no_fee_result = swap
+            .swap_to(
+                CURRENT_TS,
+                source_amount,
+                swap_source_amount,
+                swap_destination_amount,
+                0,
+                1,
+                0,
+                1,
+            )
+            .unwrap();
+        assert_eq!(
+            result.new_destination_amount - no_fee_result.new_destination_amount,
+            result.fee - result.admin_fee
+        );
+    }
+
+    #[test]
+    fn test_constant_product_curve() {
+        let curve = ConstantProductCurve;
+        let result = curve
+            .compute_swap(CURRENT_TS, 10, 100, 100, 1, 100, 0, 1)
+            .unwrap();
+        // x*y=k: (100 + 10) * new_y = 100 * 100 => new_y = 90.9.., so
+        // roughly 9 tokens leave the pool before fees
+        assert_eq!(result.new_source_amount, 110);
+        assert!(result.amount_swapped < 10);
+    }
+
+    #[test]
+    fn test_flat_curve_swaps_one_to_one_minus_fee() {
+        let curve = FlatCurve;
+        let result = curve
+            .compute_swap(CURRENT_TS, 1000, 100_000, 100_000, 1, 100, 0, 1)
+            .unwrap();
+        assert_eq!(result.amount_swapped, 990); // 1% fee taken out
+        assert_eq!(result.new_source_amount, 101_000);
+        assert_eq!(result.new_destination_amount, 100_000 - 990);
+    }
+
+    #[test]
+    fn test_swap_curve_dispatches_to_its_calculator() {
+        let swap_curve = SwapCurve::new(CurveType::Flat).unwrap();
+        let result = swap_curve
+            .compute_swap(CURRENT_TS, 1000, 100_000, 100_000, 1, 100, 0, 1)
+            .unwrap();
+        assert_eq!(result.amount_swapped, 990);
+    }
+
+    #[test]
+    fn test_swap_curve_new_builds_the_matching_calculator() {
+        let stable = SwapCurve::new(CurveType::Stable {
+            initial_amp_factor: 100,
+            target_amp_factor: 100,
+            start_ramp_ts: 0,
+            stop_ramp_ts: MIN_RAMP_DURATION,
+        })
+        .unwrap();
+        assert_eq!(
+            stable
+                .compute_swap(CURRENT_TS, 10, 100, 100, 0, 1, 0, 1)
+                .unwrap()
+                .amount_swapped,
+            fixed_swap(100)
+                .compute_swap(CURRENT_TS, 10, 100, 100, 0, 1, 0, 1)
+                .unwrap()
+                .amount_swapped
+        );
+
+        // Invalid StableSwap parameters are rejected up front instead of
+        // producing a `SwapCurve` whose tag and calculator disagree
+        assert_eq!(
+            SwapCurve::new(CurveType::Stable {
+                initial_amp_factor: 0,
+                target_amp_factor: 100,
+                start_ramp_ts: 0,
+                stop_ramp_ts: MIN_RAMP_DURATION,
+            })
+            .unwrap_err(),
+            SwapError::InvalidInput
+        );
+    }
+
+    #[test]
+    fn test_stable_swap_trading_tokens_to_pool_tokens() {
+        let swap = fixed_swap(100);
+        let pool_tokens = swap
+            .trading_tokens_to_pool_tokens(10_000, 100_000, 1_000_000, RoundDirection::Ceiling)
+            .unwrap();
+        assert_eq!(pool_tokens, 100_000);
+    }
+
+    #[test]
+    fn test_stable_swap_pool_tokens_to_trading_tokens() {
+        let swap = fixed_swap(100);
+        let (token_a, token_b) = swap
+            .pool_tokens_to_trading_tokens(
+                100_000,
+                1_000_000,
+                50_000,
+                60_000,
+                RoundDirection::Floor,
+            )
+            .unwrap();
+        assert_eq!(token_a, 5_000);
+        assert_eq!(token_b, 6_000);
+    }
+
+    #[test]
+    fn test_constant_product_curve_trading_tokens_to_pool_tokens() {
+        let curve = ConstantProductCurve;
+        let pool_tokens = curve
+            .trading_tokens_to_pool_tokens(10_000, 100_000, 1_000_000, RoundDirection::Ceiling)
+            .unwrap();
+        assert_eq!(pool_tokens, 100_000);
+    }
+
+    #[test]
+    fn test_constant_product_curve_pool_tokens_to_trading_tokens() {
+        let curve = ConstantProductCurve;
+        let (token_a, token_b) = curve
+            .pool_tokens_to_trading_tokens(
+                100_000,
+                1_000_000,
+                50_000,
+                60_000,
+                RoundDirection::Floor,
+            )
+            .unwrap();
+        assert_eq!(token_a, 5_000);
+        assert_eq!(token_b, 6_000);
+    }
+
+    #[test]
+    fn test_flat_curve_trading_tokens_to_pool_tokens() {
+        let curve = FlatCurve;
+        let pool_tokens = curve
+            .trading_tokens_to_pool_tokens(10_000, 100_000, 1_000_000, RoundDirection::Ceiling)
+            .unwrap();
+        assert_eq!(pool_tokens, 100_000);
+    }
+
+    #[test]
+    fn test_flat_curve_pool_tokens_to_trading_tokens() {
+        let curve = FlatCurve;
+        let (token_a, token_b) = curve
+            .pool_tokens_to_trading_tokens(
+                100_000,
+                1_000_000,
+                50_000,
+                60_000,
+                RoundDirection::Floor,
+            )
+            .unwrap();
+        assert_eq!(token_a, 5_000);
+        assert_eq!(token_b, 6_000);
+    }
+
     fn check_withdraw_one(
         amp_factor: u64,
         pool_token_amount: u64,
@@ -386,15 +1265,21 @@ mod tests {
         swap_quote_amount: u64,
     ) {
         let n_coin = 2;
-        let swap = StableSwap { amp_factor };
-        let result = swap.compute_withdraw_one(
-            pool_token_amount,
-            pool_token_supply,
-            swap_base_amount,
-            swap_quote_amount,
-            MODEL_FEE_NUMERATOR,
-            MODEL_FEE_DENOMINATOR,
-        );
+        let swap = fixed_swap(amp_factor as u128);
+        let result = swap
+            .compute_withdraw_one(
+                CURRENT_TS,
+                pool_token_amount,
+                pool_token_supply,
+                swap_base_amount,
+                swap_quote_amount,
+                MODEL_FEE_NUMERATOR as u64,
+                MODEL_FEE_DENOMINATOR as u64,
+                0,
+                1,
+                RoundDirection::Floor,
+            )
+            .unwrap();
         let model = Model::new_with_pool_tokens(
             amp_factor,
             vec![swap_base_amount, swap_quote_amount],
@@ -407,6 +1292,61 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_compute_withdraw_one_round_direction_favors_the_pool() {
+        let swap = fixed_swap(100);
+        let pool_token_amount = 12345;
+        let pool_token_supply = 777777;
+        let swap_base_amount = 50_000_000;
+        let swap_quote_amount = 50_000_000;
+
+        let withdraw_with = |round_direction| {
+            swap.compute_withdraw_one(
+                CURRENT_TS,
+                pool_token_amount,
+                pool_token_supply,
+                swap_base_amount,
+                swap_quote_amount,
+                0,
+                1,
+                0,
+                1,
+                round_direction,
+            )
+            .unwrap()
+            .0
+        };
+
+        // `Floor` must never pay out more than `Ceiling`, or it isn't
+        // actually the direction that favors the pool
+        assert!(withdraw_with(RoundDirection::Floor) <= withdraw_with(RoundDirection::Ceiling));
+    }
+
+    #[test]
+    fn test_compute_withdraw_one_admin_fee_rounds_down() {
+        // Same split convention as `swap_to`: the admin's cut truncates
+        // toward zero instead of rounding up, so it never skims an extra
+        // unit from the LPs' share of the fee
+        let swap = fixed_swap(100);
+        let (_, lp_fee, admin_fee) = swap
+            .compute_withdraw_one(
+                CURRENT_TS,
+                12345,
+                777777,
+                50_000_000,
+                50_000_000,
+                2,
+                4,
+                1,
+                1000,
+                RoundDirection::Floor,
+            )
+            .unwrap();
+        let total_fee = lp_fee + admin_fee;
+
+        assert_eq!(admin_fee, total_fee * 1 / 1000);
+    }
+
     // #[test]
     // fn test_compute_withdraw_one() {
     //     let pool_token_amount = 10000;
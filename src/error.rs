@@ -0,0 +1,28 @@
+//! Error types
+
+use std::fmt;
+
+/// Errors that can occur during swap curve calculations
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum SwapError {
+    /// The curve calculation failed, e.g. a division by zero or an
+    /// intermediate value that could not be represented
+    CalculationFailure,
+    /// A value did not fit into the target integer type
+    ConversionFailure,
+    /// An argument passed to a constructor or calculation was out of its
+    /// allowed range
+    InvalidInput,
+}
+
+impl fmt::Display for SwapError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SwapError::CalculationFailure => write!(f, "Curve calculation failed"),
+            SwapError::ConversionFailure => write!(f, "Value did not fit into the target type"),
+            SwapError::InvalidInput => write!(f, "Argument was out of its allowed range"),
+        }
+    }
+}
+
+impl std::error::Error for SwapError {}
@@ -0,0 +1,56 @@
+//! Small numeric helpers shared across curve calculations
+
+use crate::error::SwapError;
+use std::convert::TryInto;
+
+/// Convert a `u64` into the `u128` base type used throughout the curve math
+pub fn to_u128(val: u64) -> Result<u128, SwapError> {
+    Ok(val.into())
+}
+
+/// Convert a `u128` back down into a `u64`, erroring instead of truncating
+pub fn to_u64(val: u128) -> Result<u64, SwapError> {
+    val.try_into().map_err(|_| SwapError::ConversionFailure)
+}
+
+/// Which way a division should round when the result isn't exact
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum RoundDirection {
+    /// Round toward zero; used for amounts paid out of the pool
+    Floor,
+    /// Round away from zero; used for amounts owed to the pool
+    Ceiling,
+}
+
+/// Checked division that rounds in the given direction instead of always
+/// truncating toward zero
+pub fn checked_div_round(
+    numerator: u128,
+    denominator: u128,
+    round_direction: RoundDirection,
+) -> Option<u128> {
+    match round_direction {
+        RoundDirection::Floor => numerator.checked_div(denominator),
+        RoundDirection::Ceiling => numerator
+            .checked_add(denominator)?
+            .checked_sub(1)?
+            .checked_div(denominator),
+    }
+}
+
+/// Scale `amount` by `rate_numerator / rate_denominator`, rounding in the
+/// given direction. This is the single source of truth for pool-token <->
+/// trading-token rate conversions, shared by `SwapCalculator`'s default
+/// methods and `PoolTokenConverter`, so the two can't silently diverge.
+pub fn scale_amount(
+    amount: u128,
+    rate_numerator: u128,
+    rate_denominator: u128,
+    round_direction: RoundDirection,
+) -> Option<u128> {
+    checked_div_round(
+        amount.checked_mul(rate_numerator)?,
+        rate_denominator,
+        round_direction,
+    )
+}
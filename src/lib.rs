@@ -0,0 +1,6 @@
+//! An implementation of a StableSwap-style AMM curve
+
+pub mod bn;
+pub mod curve;
+pub mod error;
+pub mod helpers;